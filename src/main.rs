@@ -3,7 +3,7 @@
 
 use std::time::{Instant};
 use std::net::TcpStream;
-use std::io::{BufReader,BufWriter,BufRead,Write};
+use std::io::{BufWriter,Read,Write};
 
 enum Request {
     Ping,
@@ -25,17 +25,29 @@ enum Request {
         key: String,
         start: i64,
         end: i64
-    }
+    },
+    Raw {
+        args: Vec<Vec<u8>>,
+    },
+    Subscribe {
+        channels: Vec<String>,
+    },
+    Psubscribe {
+        patterns: Vec<String>,
+    },
+    Unsubscribe {
+        channels: Vec<String>,
+    },
 }
 
 #[derive(Debug)]
 enum Response {
-    SimpleString { value: String },
-    Error { value: String },
+    SimpleString { value: Vec<u8> },
+    Error { value: Vec<u8> },
     Integer { value: i64 },
     BulkString {
         length: i64,
-        value: String,
+        value: Vec<u8>,
     },
     Array {
         length: i64,
@@ -43,139 +55,652 @@ enum Response {
     },
 
     Unknown {
-        resp_type: Option<char>,
-        value: Option<String>,
+        resp_type: Option<u8>,
+        value: Option<Vec<u8>>,
     },
 }
 
-struct RedisClient {
-    writer: BufWriter<TcpStream>,
-    reader: BufReader<TcpStream>,
+impl Response {
+    // Lossy view of a textual reply, for callers that don't need exact bytes.
+    fn as_str_lossy(&self) -> Option<std::borrow::Cow<'_, str>> {
+        match self {
+            Response::SimpleString { value } => Some(String::from_utf8_lossy(value)),
+            Response::BulkString { value, .. } => Some(String::from_utf8_lossy(value)),
+            Response::Error { value } => Some(String::from_utf8_lossy(value)),
+            _ => None,
+        }
+    }
 }
 
+#[derive(Debug)]
+struct RedisError {
+    message: String,
+}
 
-impl RedisClient {
+impl std::fmt::Display for RedisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
 
-    fn filter_eol(x: &char) -> bool {
-        !(*x == '\r' || *x == '\n')
+impl std::error::Error for RedisError {}
+
+impl From<std::io::Error> for RedisError {
+    fn from(err: std::io::Error) -> RedisError {
+        RedisError { message: err.to_string() }
     }
+}
 
-    fn get_result(iter: std::str::Chars) -> String {
-        iter
-            .filter(RedisClient::filter_eol)
-            .collect()
+impl RedisError {
+    fn from_error_response(value: Vec<u8>) -> RedisError {
+        RedisError { message: String::from_utf8_lossy(&value).into_owned() }
     }
 
-    fn get_length(iter: std::str::Chars) -> i64 {
-        iter
-            .filter(RedisClient::filter_eol)
-            .collect::<String>()
-            .parse()
-            .expect("Should be a number")
+    fn unexpected(expected: &str, response: &Response) -> RedisError {
+        RedisError { message: format!("Expected {} reply, got {:?}", expected, response) }
+    }
+}
+
+// Converts a raw `Response` into a typed value, turning a RESP `-ERR ...`
+// reply into an `Err` instead of forcing every caller to match on `Response`.
+trait FromRedisValue: Sized {
+    fn from_response(response: Response) -> Result<Self, RedisError>;
+}
+
+impl FromRedisValue for i64 {
+    fn from_response(response: Response) -> Result<i64, RedisError> {
+        match response {
+            Response::Integer { value } => Ok(value),
+            Response::Error { value } => Err(RedisError::from_error_response(value)),
+            other => Err(RedisError::unexpected("integer", &other)),
+        }
+    }
+}
+
+impl FromRedisValue for String {
+    fn from_response(response: Response) -> Result<String, RedisError> {
+        match response {
+            Response::BulkString { value, .. } => Ok(String::from_utf8_lossy(&value).into_owned()),
+            Response::SimpleString { value } => Ok(String::from_utf8_lossy(&value).into_owned()),
+            Response::Error { value } => Err(RedisError::from_error_response(value)),
+            other => Err(RedisError::unexpected("string", &other)),
+        }
+    }
+}
+
+impl FromRedisValue for Option<String> {
+    fn from_response(response: Response) -> Result<Option<String>, RedisError> {
+        match response {
+            Response::BulkString { length, .. } if length < 0 => Ok(None),
+            Response::Error { value } => Err(RedisError::from_error_response(value)),
+            other => String::from_response(other).map(Some),
+        }
+    }
+}
+
+impl FromRedisValue for Vec<String> {
+    fn from_response(response: Response) -> Result<Vec<String>, RedisError> {
+        match response {
+            Response::Array { value, .. } => value
+                .into_iter()
+                .map(|item| String::from_response(*item))
+                .collect(),
+            Response::Error { value } => Err(RedisError::from_error_response(value)),
+            other => Err(RedisError::unexpected("array", &other)),
+        }
+    }
+}
+
+impl FromRedisValue for bool {
+    fn from_response(response: Response) -> Result<bool, RedisError> {
+        match response {
+            Response::Integer { value } => Ok(value != 0),
+            Response::SimpleString { value } => Ok(value == b"OK"),
+            Response::Error { value } => Err(RedisError::from_error_response(value)),
+            other => Err(RedisError::unexpected("boolean-convertible", &other)),
+        }
+    }
+}
+
+// A reusable read buffer of READ_BUFFER_SIZE bytes: bytes are fetched from the
+// socket in bounded chunks, RESP values are parsed out of what's buffered, and
+// any partial value left at the end is compacted to the front before the next
+// read. It is doubled only when a single value doesn't fit, and never shrunk.
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+struct ReadBuffer {
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+}
+
+impl ReadBuffer {
+    fn new() -> ReadBuffer {
+        ReadBuffer {
+            buf: vec![0; READ_BUFFER_SIZE],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    fn filled(&self) -> &[u8] {
+        &self.buf[self.start..self.end]
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.start += n;
+    }
+
+    fn compact(&mut self) {
+        if self.start > 0 {
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+    }
+
+    // Pulls in another chunk, growing the buffer if a single value doesn't
+    // fit in READ_BUFFER_SIZE. Returns the number of bytes read (0 = EOF).
+    fn fill(&mut self, stream: &mut TcpStream) -> std::io::Result<usize> {
+        self.compact();
+        if self.end == self.buf.len() {
+            self.buf.resize(self.buf.len() * 2, 0);
+        }
+        let read = stream.read(&mut self.buf[self.end..])?;
+        self.end += read;
+        Ok(read)
+    }
+}
+
+// Connection settings parsed from a `redis://[:password@]host:port/db` URL,
+// with `REDIS_PASSWORD`/`REDIS_DB`/`REDIS_NAMESPACE` env vars layered on top.
+struct RedisConfig {
+    host: String,
+    port: u16,
+    password: Option<String>,
+    db: Option<i64>,
+    namespace: Option<String>,
+}
+
+impl RedisConfig {
+    fn from_url(url: &str) -> RedisConfig {
+        let rest = url.strip_prefix("redis://").expect("Redis URL must start with redis://");
+
+        let (auth, rest) = match rest.split_once('@') {
+            Some((auth, rest)) => (Some(auth), rest),
+            None => (None, rest),
+        };
+        let password = auth
+            .and_then(|auth| auth.strip_prefix(':'))
+            .filter(|password| !password.is_empty())
+            .map(String::from);
+
+        let (hostport, db) = match rest.split_once('/') {
+            Some((hostport, db)) => (hostport, Some(db)),
+            None => (rest, None),
+        };
+        let (host, port) = hostport.split_once(':').expect("Redis URL must include a port");
+
+        RedisConfig {
+            host: host.to_string(),
+            port: port.parse().expect("Should be a number"),
+            password,
+            db: db.filter(|db| !db.is_empty()).map(|db| db.parse().expect("Should be a number")),
+            namespace: None,
+        }
+    }
+
+    // Builds on `REDIS_URL`, falling back to a local default when it isn't
+    // set, then layers `REDIS_PASSWORD`/`REDIS_DB`/`REDIS_NAMESPACE` on top.
+    fn from_env() -> RedisConfig {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| String::from("redis://127.0.0.1:6379"));
+        let mut config = RedisConfig::from_url(&url);
+
+        if let Ok(password) = std::env::var("REDIS_PASSWORD") {
+            config.password = Some(password);
+        }
+        if let Ok(db) = std::env::var("REDIS_DB") {
+            config.db = Some(db.parse().expect("Should be a number"));
+        }
+        if let Ok(namespace) = std::env::var("REDIS_NAMESPACE") {
+            config.namespace = Some(namespace);
+        }
+
+        config
     }
 
+    fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+struct RedisClient {
+    writer: BufWriter<TcpStream>,
+    stream: TcpStream,
+    read_buf: ReadBuffer,
+    namespace: Option<String>,
+}
+
+
+impl RedisClient {
+
     pub fn new(url: &str) -> Result<RedisClient, std::io::Error> {
         let stream = TcpStream::connect(url)?;
         Ok(RedisClient {
             writer: BufWriter::new(stream.try_clone()?),
-            reader: BufReader::new(stream.try_clone()?),
+            stream: stream.try_clone()?,
+            read_buf: ReadBuffer::new(),
+            namespace: None,
         })
     }
 
+    // Connects using a `RedisConfig` and performs the handshake a production
+    // consumer expects: AUTH if a password is configured, a PING to confirm
+    // the server is actually speaking RESP, then SELECT if a DB was given.
+    pub fn connect(config: RedisConfig) -> Result<RedisClient, std::io::Error> {
+        let mut client = RedisClient::new(&config.address())?;
+        client.namespace = config.namespace;
+
+        if let Some(password) = config.password {
+            if let Response::Error { value } = client.send_command(Request::Raw {
+                args: vec![b"AUTH".to_vec(), password.into_bytes()],
+            })? {
+                return Err(RedisClient::handshake_error("AUTH failed", &value));
+            }
+        }
+
+        match client.send_command(Request::Ping)? {
+            Response::SimpleString { value } if value == b"PONG" => {},
+            Response::Error { value } => return Err(RedisClient::handshake_error("PING failed", &value)),
+            other => return Err(RedisClient::handshake_error(
+                "Unexpected PING reply during handshake",
+                format!("{:?}", other).as_bytes(),
+            )),
+        }
+
+        if let Some(db) = config.db {
+            if let Response::Error { value } = client.send_command(Request::Raw {
+                args: vec![b"SELECT".to_vec(), db.to_string().into_bytes()],
+            })? {
+                return Err(RedisClient::handshake_error("SELECT failed", &value));
+            }
+        }
+
+        Ok(client)
+    }
+
+    fn handshake_error(context: &str, detail: &[u8]) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("{}: {}", context, String::from_utf8_lossy(detail)),
+        )
+    }
+
+    // Rewrites key-bearing requests to carry the configured namespace prefix,
+    // leaving values, channels and raw commands untouched.
+    fn apply_namespace(&self, command: Request) -> Request {
+        let namespace = match &self.namespace {
+            Some(namespace) => namespace,
+            None => return command,
+        };
+
+        match command {
+            Request::Get { key } => Request::Get { key: format!("{}{}", namespace, key) },
+            Request::Set { key, value } => Request::Set { key: format!("{}{}", namespace, key), value },
+            Request::Incr { key } => Request::Incr { key: format!("{}{}", namespace, key) },
+            Request::Lpush { key, value } => Request::Lpush { key: format!("{}{}", namespace, key), value },
+            Request::Lrange { key, start, end } => Request::Lrange { key: format!("{}{}", namespace, key), start, end },
+            other => other,
+        }
+    }
+
     pub fn send_command(&mut self, command: Request) -> Result<Response, std::io::Error> {
+        let command = self.apply_namespace(command);
         let req = RedisClient::get_command(command);
-        self.writer.write_all(req.as_bytes())?;
-        self.writer.write_all(b"\r\n")?;
+        self.writer.write_all(&req)?;
         self.writer.flush()?;
-        Ok(RedisClient::parse_response(&mut self.reader))
+        Ok(RedisClient::parse_response(&mut self.stream, &mut self.read_buf))
     }
 
-    fn escape(input: &str) -> String {
-        input.replace("\"", "\\\"")
+    // Like `send_command`, but converts the reply into `T`, turning a RESP
+    // `-ERR ...` reply (or a reply of the wrong shape) into a `RedisError`.
+    pub fn query<T: FromRedisValue>(&mut self, command: Request) -> Result<T, RedisError> {
+        let response = self.send_command(command)?;
+        T::from_response(response)
+    }
+
+    // Puts the connection into subscribe mode and hands it over to a
+    // `Subscription`: from here on the socket is a unidirectional stream of
+    // server-pushed arrays, not one reply per command, so the client gives
+    // up ownership rather than keep offering `send_command`.
+    pub fn subscribe(mut self, channels: Vec<String>) -> Result<Subscription, std::io::Error> {
+        let req = RedisClient::get_command(Request::Subscribe { channels });
+        self.writer.write_all(&req)?;
+        self.writer.flush()?;
+        Ok(Subscription {
+            writer: self.writer,
+            stream: self.stream,
+            read_buf: self.read_buf,
+        })
     }
 
-    fn get_command(command: Request) -> String {
+    pub fn psubscribe(mut self, patterns: Vec<String>) -> Result<Subscription, std::io::Error> {
+        let req = RedisClient::get_command(Request::Psubscribe { patterns });
+        self.writer.write_all(&req)?;
+        self.writer.flush()?;
+        Ok(Subscription {
+            writer: self.writer,
+            stream: self.stream,
+            read_buf: self.read_buf,
+        })
+    }
+
+    // Batches many commands into a single round-trip: queued commands are
+    // written without flushing, one flush sends them all, then exactly as
+    // many replies are read back, in order.
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline {
+            client: self,
+            commands: 0,
+            error: None,
+        }
+    }
+
+    fn bulk_bytes(response: Response) -> Option<Vec<u8>> {
+        match response {
+            Response::BulkString { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    fn command_args(command: Request) -> Vec<Vec<u8>> {
         match command {
-            Request::Ping => String::from("PING"),
-            Request::Get { key } => format!(
-                "GET \"{}\"",
-                RedisClient::escape(&key)
-            ),
-            Request::Set { key, value } => format!(
-                "SET \"{}\" \"{}\"",
-                RedisClient::escape(&key),
-                RedisClient::escape(&value),
-            ),
-            Request::Incr { key } => format!(
-                "INCR \"{}\"",
-                RedisClient::escape(&key),
-            ),
-            Request::Lpush { key, value } => format!(
-                "LPUSH \"{}\" \"{}\"",
-                RedisClient::escape(&key),
-                RedisClient::escape(&value),
-            ),
-            Request::Lrange { key, start, end } => format!(
-                "LRANGE \"{}\" \"{}\" \"{}\"",
-                key, start, end,
-            ),
-        }
-    }
-
-
-    fn parse_response(reader: &mut BufReader<TcpStream>) -> Response {
-        let mut line = String::new();
-        reader.read_line(&mut line).expect("Can't read line from redis");
-
-        let mut chars = line.chars();
-        match chars.next() {
-            Some('+') => Response::SimpleString {
-                value: RedisClient::get_result(chars)
+            Request::Ping => vec![b"PING".to_vec()],
+            Request::Get { key } => vec![
+                b"GET".to_vec(),
+                key.into_bytes(),
+            ],
+            Request::Set { key, value } => vec![
+                b"SET".to_vec(),
+                key.into_bytes(),
+                value.into_bytes(),
+            ],
+            Request::Incr { key } => vec![
+                b"INCR".to_vec(),
+                key.into_bytes(),
+            ],
+            Request::Lpush { key, value } => vec![
+                b"LPUSH".to_vec(),
+                key.into_bytes(),
+                value.into_bytes(),
+            ],
+            Request::Lrange { key, start, end } => vec![
+                b"LRANGE".to_vec(),
+                key.into_bytes(),
+                start.to_string().into_bytes(),
+                end.to_string().into_bytes(),
+            ],
+            Request::Raw { args } => args,
+            Request::Subscribe { channels } => {
+                let mut args = vec![b"SUBSCRIBE".to_vec()];
+                args.extend(channels.into_iter().map(String::into_bytes));
+                args
+            },
+            Request::Psubscribe { patterns } => {
+                let mut args = vec![b"PSUBSCRIBE".to_vec()];
+                args.extend(patterns.into_iter().map(String::into_bytes));
+                args
             },
-            Some('-') => Response::Error {
-                value: RedisClient::get_result(chars)
+            Request::Unsubscribe { channels } => {
+                let mut args = vec![b"UNSUBSCRIBE".to_vec()];
+                args.extend(channels.into_iter().map(String::into_bytes));
+                args
             },
-            Some(':') => Response::Integer {
-                value: RedisClient::get_length(chars)
+        }
+    }
+
+    // Encodes a command as a RESP array of bulk strings, e.g. for `SET a b`:
+    // *3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\nb\r\n -- binary-safe, no quoting/escaping needed.
+    fn get_command(command: Request) -> Vec<u8> {
+        let args = RedisClient::command_args(command);
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+        for arg in args {
+            buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            buf.extend_from_slice(&arg);
+            buf.extend_from_slice(b"\r\n");
+        }
+        buf
+    }
+
+
+    fn find_crlf(buf: &[u8]) -> Option<usize> {
+        buf.windows(2).position(|w| w == b"\r\n")
+    }
+
+    fn parse_i64(buf: &[u8]) -> i64 {
+        std::str::from_utf8(buf)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .expect("Should be a number")
+    }
+
+    // Parses at most one RESP value from the front of `buf`. Returns the
+    // value together with the number of bytes it consumed, or `None` if
+    // `buf` doesn't yet hold a complete value (caller should read more).
+    fn try_parse(buf: &[u8]) -> Option<(Response, usize)> {
+        let resp_type = *buf.first()?;
+        let rest = &buf[1..];
+
+        match resp_type {
+            b'+' | b'-' | b':' => {
+                let pos = RedisClient::find_crlf(rest)?;
+                let line = &rest[..pos];
+                let consumed = 1 + pos + 2;
+                let response = match resp_type {
+                    b'+' => Response::SimpleString { value: line.to_vec() },
+                    b'-' => Response::Error { value: line.to_vec() },
+                    b':' => Response::Integer { value: RedisClient::parse_i64(line) },
+                    _ => unreachable!(),
+                };
+                Some((response, consumed))
             },
-            Some('$') => {
-                let length = RedisClient::get_length(chars);
-                let mut buf = String::new();
-                reader.read_line(&mut buf).expect("Can't read content for BulkString");
-                Response::BulkString {
-                    length,
-                    value: RedisClient::get_result(buf.chars()),
+            b'$' => {
+                let pos = RedisClient::find_crlf(rest)?;
+                let length = RedisClient::parse_i64(&rest[..pos]);
+                let header_len = 1 + pos + 2;
+
+                if length < 0 {
+                    // Nil bulk string.
+                    return Some((Response::BulkString { length, value: Vec::new() }, header_len));
+                }
+
+                let length = length as usize;
+                let body = &buf[header_len..];
+                if body.len() < length + 2 {
+                    return None;
                 }
+
+                let value = body[..length].to_vec();
+                Some((
+                    Response::BulkString { length: length as i64, value },
+                    header_len + length + 2,
+                ))
             },
-            Some('*') => {
-                let length = RedisClient::get_length(chars);
-                
-                let mut vector : Vec<Box<Response>> = Vec::with_capacity(length as usize);
+            b'*' => {
+                let pos = RedisClient::find_crlf(rest)?;
+                let length = RedisClient::parse_i64(&rest[..pos]);
+                let mut offset = 1 + pos + 2;
+
+                if length < 0 {
+                    return Some((Response::Array { length, value: Vec::new() }, offset));
+                }
 
+                let mut items: Vec<Box<Response>> = Vec::with_capacity(length as usize);
                 for _ in 0..length {
-                    vector.push(Box::new(
-                        RedisClient::parse_response(reader)
-                    ));
+                    let (item, consumed) = RedisClient::try_parse(&buf[offset..])?;
+                    items.push(Box::new(item));
+                    offset += consumed;
                 }
 
-                Response::Array {
-                    length,
-                    value: vector
+                Some((Response::Array { length, value: items }, offset))
+            },
+            _ => {
+                let pos = RedisClient::find_crlf(rest)?;
+                Some((
+                    Response::Unknown {
+                        resp_type: Some(resp_type),
+                        value: Some(rest[..pos].to_vec()),
+                    },
+                    1 + pos + 2,
+                ))
+            },
+        }
+    }
+
+    fn parse_response(stream: &mut TcpStream, read_buf: &mut ReadBuffer) -> Response {
+        loop {
+            if let Some((response, consumed)) = RedisClient::try_parse(read_buf.filled()) {
+                read_buf.consume(consumed);
+                return response;
+            }
+
+            let read = read_buf.fill(stream).expect("Can't read from redis");
+            if read == 0 {
+                panic!("Connection closed by redis");
+            }
+        }
+    }
+}
+
+struct Pipeline<'a> {
+    client: &'a mut RedisClient,
+    commands: usize,
+    error: Option<std::io::Error>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn add(mut self, command: Request) -> Self {
+        if self.error.is_none() {
+            let command = self.client.apply_namespace(command);
+            let req = RedisClient::get_command(command);
+            match self.client.writer.write_all(&req) {
+                Ok(()) => self.commands += 1,
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self
+    }
+
+    pub fn execute(self) -> Result<Vec<Response>, std::io::Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        self.client.writer.flush()?;
+
+        let mut responses = Vec::with_capacity(self.commands);
+        for _ in 0..self.commands {
+            responses.push(RedisClient::parse_response(&mut self.client.stream, &mut self.client.read_buf));
+        }
+        Ok(responses)
+    }
+}
+
+#[derive(Debug)]
+struct PubSubMessage {
+    kind: String,
+    pattern: Option<Vec<u8>>,
+    channel: Vec<u8>,
+    payload: Vec<u8>,
+}
+
+struct Subscription {
+    writer: BufWriter<TcpStream>,
+    stream: TcpStream,
+    read_buf: ReadBuffer,
+}
+
+impl Subscription {
+    // Lets callers poll instead of deadlocking: with a read timeout set,
+    // `next_message` returns `Ok(None)` on `WouldBlock`/`TimedOut` rather than blocking forever.
+    pub fn set_read_timeout(&mut self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    pub fn unsubscribe(&mut self, channels: Vec<String>) -> Result<(), std::io::Error> {
+        let req = RedisClient::get_command(Request::Unsubscribe { channels });
+        self.writer.write_all(&req)?;
+        self.writer.flush()
+    }
+
+    // `Ok(None)` means "nothing new yet" (only possible with a read timeout
+    // set via `set_read_timeout`) -- keep polling. `Err` with
+    // `ErrorKind::UnexpectedEof` means Redis closed the connection -- stop
+    // polling, it will never produce another message.
+    pub fn next_message(&mut self) -> Result<Option<PubSubMessage>, std::io::Error> {
+        loop {
+            if let Some((response, consumed)) = RedisClient::try_parse(self.read_buf.filled()) {
+                self.read_buf.consume(consumed);
+                // Subscribe/unsubscribe confirmations aren't message/pmessage
+                // pushes; skip them and keep reading for the next push.
+                if let Some(message) = Subscription::decode_push(response) {
+                    return Ok(Some(message));
                 }
+                continue;
+            }
+
+            match self.read_buf.fill(&mut self.stream) {
+                Ok(0) => return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Redis closed the pub/sub connection",
+                )),
+                Ok(_) => {},
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut => return Ok(None),
+                Err(e) => return Err(e),
             }
-            Some(unknown_type) => Response::Unknown {
-                resp_type: Some(unknown_type),
-                value: Some(chars.collect()),
+        }
+    }
+
+    fn decode_push(response: Response) -> Option<PubSubMessage> {
+        let items = match response {
+            Response::Array { value, .. } => value,
+            _ => return None,
+        };
+        let mut iter = items.into_iter();
+
+        let kind = (*iter.next()?).as_str_lossy()?.into_owned();
+        match kind.as_str() {
+            "message" => {
+                let channel = RedisClient::bulk_bytes(*iter.next()?)?;
+                let payload = RedisClient::bulk_bytes(*iter.next()?)?;
+                Some(PubSubMessage { kind, pattern: None, channel, payload })
+            },
+            "pmessage" => {
+                let pattern = RedisClient::bulk_bytes(*iter.next()?)?;
+                let channel = RedisClient::bulk_bytes(*iter.next()?)?;
+                let payload = RedisClient::bulk_bytes(*iter.next()?)?;
+                Some(PubSubMessage { kind, pattern: Some(pattern), channel, payload })
             },
-            None => Response::Unknown { resp_type: None, value: None },
+            _ => None,
         }
     }
 }
 
+impl Iterator for Subscription {
+    type Item = PubSubMessage;
+
+    fn next(&mut self) -> Option<PubSubMessage> {
+        self.next_message().ok().flatten()
+    }
+}
+
 
 fn main() {
-    let url = "127.0.0.1:6379";
-    let mut client = RedisClient::new(url).unwrap();
+    let config = RedisConfig::from_env();
+    let mut client = RedisClient::connect(config).unwrap();
 
     println!("simple get/set");
     println!("{:?}", client.send_command(Request::Ping).unwrap());
@@ -203,6 +728,9 @@ fn main() {
             key: String::from("myincr")
         }
     ).unwrap());
+
+    let myincr: i64 = client.query(Request::Incr { key: String::from("myincr") }).unwrap();
+    println!("{:?}", myincr);
     println!();
 
     println!("error");
@@ -213,6 +741,32 @@ fn main() {
     ).unwrap());
     println!();
 
+    println!("pub/sub");
+    let subscriber = RedisClient::connect(RedisConfig::from_env()).unwrap();
+    let mut subscription = subscriber.subscribe(vec![String::from("demo-channel")]).unwrap();
+    subscription.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+
+    client.send_command(Request::Raw {
+        args: vec![b"PUBLISH".to_vec(), b"demo-channel".to_vec(), b"hello pubsub".to_vec()],
+    }).unwrap();
+
+    println!("{:?}", subscription.next_message().unwrap());
+
+    subscription.unsubscribe(vec![String::from("demo-channel")]).unwrap();
+    println!();
+
+    println!("pattern pub/sub");
+    let psubscriber = RedisClient::connect(RedisConfig::from_env()).unwrap();
+    let mut psubscription = psubscriber.psubscribe(vec![String::from("demo-*")]).unwrap();
+    psubscription.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+
+    client.send_command(Request::Raw {
+        args: vec![b"PUBLISH".to_vec(), b"demo-channel".to_vec(), b"hello pattern".to_vec()],
+    }).unwrap();
+
+    println!("{:?}", psubscription.next_message().unwrap());
+    println!();
+
     println!("simple list");
     println!("{:?}", client.send_command(
         Request::Lpush {
@@ -253,4 +807,18 @@ fn main() {
         ).unwrap();
     };
     println!("{:?}", now.elapsed());
+    println!();
+
+    println!("bench (pipelined)");
+    let now = Instant::now();
+    for _ in 0..1_000 {
+        let mut pipeline = client.pipeline();
+        for _ in 0..100 {
+            pipeline = pipeline
+                .add(Request::Set { key: bench_key.clone(), value: "10".to_string() })
+                .add(Request::Get { key: bench_key.clone() });
+        }
+        pipeline.execute().unwrap();
+    };
+    println!("{:?}", now.elapsed());
 }